@@ -2,19 +2,29 @@
 //!
 //! The implementation is based on the [Adafruit CircuitPython Seesaw library](https://github.com/adafruit/Adafruit_CircuitPython_seesaw).
 //!
-//! The library is tested and used on a Raspberry Pi 3 B+ board, running Raspbian but uses interfaces
-//! from `embedded_hal` operations like sleep/delay and other system calls.
+//! The driver is built on top of `embedded_hal`'s blocking I2C and delay traits, so it runs on
+//! any MCU or board with an `embedded_hal` implementation (Raspberry Pi, RP2040, STM32, ESP32, ...).
+//! A convenience constructor for `rppal` (the crate most commonly used on a Raspberry Pi) is
+//! available behind the `raspberry-pi` feature.
+//!
+//! For reading several sensors off one bus without stacking up blocking delays, see
+//! [`SensorStateMachine`] and [`MultiSensor`] for a non-blocking, tick-driven polling mode.
+//!
+//! [`SoilSensor`] is a thin layer over [`Seesaw`], the reusable register core for the seesaw
+//! chip the sensor is built on. Reach for `Seesaw` directly if you're driving a bare seesaw
+//! breakout, or need its GPIO/ADC modules alongside the soil sensor's own readings.
 //!
 //! ## Example
 //!
 //! ```rust, ignore
 //! pub fn main(interval_ms: u64) {
 //!    use stemma_soil_sensor::SoilSensor;
-//!    use linux_embedded_hal::Delay;
+//!    use linux_embedded_hal::{Delay, I2cdev};
 //!    use embedded_hal::blocking::delay::DelayMs;
 //!
+//!    let i2c = I2cdev::new("/dev/i2c-1").unwrap();
 //!    let delay = Delay {};
-//!    let mut sensor = SoilSensor::init(delay).unwrap();
+//!    let mut sensor = SoilSensor::new(i2c, 0x36, delay).unwrap();
 //!
 //!     loop {
 //!        let temp = sensor.get_temp().unwrap();
@@ -27,82 +37,139 @@
 //!}
 //! ```
 //!
+//! On a Raspberry Pi, enabling the `raspberry-pi` feature brings back the old auto-discovering
+//! constructor, which scans the factory address range and picks the first sensor that responds:
+//!
+//! ```rust, ignore
+//! use stemma_soil_sensor::SoilSensor;
+//! use linux_embedded_hal::Delay;
+//!
+//! let delay = Delay {};
+//! let mut sensor = SoilSensor::init(delay).unwrap();
+//! ```
+//!
 //! ## Debugging
 //!
 //! There are a lot of `debug!` information in the code which will be available on debug builds.
 //! Attaching a logger and setting `RUST_LOG=debug` will yield a lot of information.
 //!
 use embedded_hal::blocking::delay::DelayUs;
-use rppal::i2c::{self, Error as I2CError, I2c};
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
 #[macro_use]
 extern crate log;
 use thiserror::Error;
 
 mod regs;
+mod seesaw;
+mod state_machine;
+#[cfg(test)]
+mod test_support;
+
+pub use seesaw::Seesaw;
+pub use state_machine::{MultiSensor, Reading, SensorStateMachine};
 
 // Let the chip get some time to process. https://github.com/adafruit/Adafruit_Seesaw/blob/8728936a5d1a0a7bf2887a82adb0828b70556a45/Adafruit_seesaw.cpp#L745
 const STD_PROCESSING_DELAY_MICROS: u16 = 125;
 
-const SENSOR_START_ADDR: u16 = 0x36;
-const SENSOR_END_ADDR: u16 = 0x39;
+#[cfg(feature = "raspberry-pi")]
+const SENSOR_START_ADDR: u8 = 0x36;
+#[cfg(feature = "raspberry-pi")]
+const SENSOR_END_ADDR: u8 = 0x39;
+
+pub type Result<T, E> = std::result::Result<T, SoilSensErr<E>>;
 
-pub type Result<T> = std::result::Result<T, SoilSensErr>;
+/// The seesaw product ID reported by a genuine STEMMA soil sensor.
+pub const STEMMA_SOIL_SENSOR_PRODUCT_ID: u16 = 4026;
 
-pub struct SoilSensor<D: DelayUs<u16>> {
-    channel: I2c,
-    delay: D,
+/// Seesaw firmware identification, read via [`SoilSensor::get_version`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeesawVersion {
+    /// The seesaw product ID. A genuine STEMMA soil sensor reports
+    /// [`STEMMA_SOIL_SENSOR_PRODUCT_ID`].
+    pub product_id: u16,
+    /// The firmware build date, seesaw-encoded.
+    pub build_date: u16,
 }
 
-impl<D: DelayUs<u16>> SoilSensor<D> {
-    /// Initializes the sensor
-    pub fn init(mut delay: D) -> Result<Self> {
-        let mut channel = i2c::I2c::new()?;
-        let mut hw_found: bool = false;
+/// Dry/wet capacitance endpoints used to turn a raw [`SoilSensor::get_capacitance`] reading
+/// into a 0-100 % moisture value via [`SoilSensor::get_moisture_percent`].
+///
+/// The defaults (`dry` = 200, `wet` = 2000) match the raw range `get_capacitance` already
+/// documents, so the sensor gives a usable percentage even before it's calibrated for a
+/// specific soil. Calibrate per-deployment with [`SoilSensor::calibrate_dry`] and
+/// [`SoilSensor::calibrate_wet`] for better accuracy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub dry: u16,
+    pub wet: u16,
+}
 
-        for adr in SENSOR_START_ADDR..=SENSOR_END_ADDR {
-            channel.set_slave_address(adr)?;
-            debug!("Connecting to adr: {:#X}", adr);
+impl Default for Calibration {
+    fn default() -> Self {
+        Calibration { dry: 200, wet: 2000 }
+    }
+}
 
-            match init::channel_init(&mut delay, &mut channel) {
-                Ok(()) => {
-                    hw_found = true;
-                    break;
-                }
-                Err(SoilSensErr::HardwareMismatch(..)) => continue,
-                Err(SoilSensErr::InvalidSlaveAddress(..)) => continue,
-                Err(e) => return Err(e),
-            }
-        }
-        if !hw_found {
-            return Err(SoilSensErr::HwNotFound);
+impl Calibration {
+    /// Linearly maps `raw` (a [`SoilSensor::get_capacitance`] reading) to 0-100 %, clamping
+    /// values outside the `dry..=wet` range.
+    ///
+    /// A degenerate calibration (`wet <= dry`, e.g. both endpoints sampled from the same
+    /// reading) can't define a slope; rather than divide by zero and leak a `NaN` out of this
+    /// public API, it's treated as a step function: 100 % once `raw` reaches `dry`, 0 % below it.
+    fn percent(&self, raw: u16) -> f32 {
+        let (dry, wet) = (self.dry as f32, self.wet as f32);
+        if wet <= dry {
+            return if raw as f32 >= dry { 100.0 } else { 0.0 };
         }
+        let percent = (raw as f32 - dry) / (wet - dry) * 100.0;
+        percent.clamp(0.0, 100.0)
+    }
+}
 
-        Ok(SoilSensor { channel, delay })
+/// A STEMMA soil sensor on an `embedded_hal` I2C bus.
+///
+/// `I2C` is any bus implementing the blocking `embedded_hal` I2C traits, and `D` is an
+/// `embedded_hal` delay implementation used to wait for the sensor to process requests. This is
+/// a thin, soil-sensor-specific layer over the reusable [`Seesaw`] core; use
+/// [`seesaw`](Self::seesaw) to reach GPIO/ADC functions of the underlying chip directly.
+pub struct SoilSensor<I2C, D> {
+    seesaw: Seesaw<I2C, D>,
+    calibration: Calibration,
+}
+
+impl<I2C, D, E> SoilSensor<I2C, D>
+where
+    I2C: Write<Error = E> + Read<Error = E> + WriteRead<Error = E>,
+    D: DelayUs<u16>,
+    E: core::fmt::Debug,
+{
+    /// Creates a sensor on the given I2C bus and slave address, checking that it responds with
+    /// the expected seesaw HW ID.
+    pub fn new(i2c: I2C, address: u8, delay: D) -> Result<Self, E> {
+        let mut seesaw = Seesaw::new(i2c, address, delay);
+        seesaw.check_hw_id()?;
+        Ok(SoilSensor { seesaw, calibration: Calibration::default() })
     }
 
-    /// Creates an instance from a pre-set channel. Useful if you want to communicate with
-    /// the sensors through a multiplexer or if your sensor for some reason is not in the standard
-    /// address range or needs some additional initialization before communicating with the sensor.
-    ///
-    /// This method still initializes the sensor and performs the necessary checks.
-    pub fn init_with_channel(mut delay: D, channel: I2c) -> Result<Self> {
-        // Initialize sensor.
-        let mut channel = channel;
-        init::channel_init(&mut delay, &mut channel)?;
-        Ok(SoilSensor { channel, delay })
+    /// Gives access to the reusable seesaw core underneath this sensor, for modules (GPIO, ADC,
+    /// ...) that aren't specific to the STEMMA soil sensor.
+    pub fn seesaw(&mut self) -> &mut Seesaw<I2C, D> {
+        &mut self.seesaw
     }
 
     /// Reads the temperature off the soil sensor. The temperature is in Celsius.
     ///
     /// The temperature sensor is not high precision but should be indicate the temperature
     /// +/- 2 degrees.
-    pub fn get_temp(&mut self) -> Result<f32> {
-        let l_reg = regs::base::SEESAW_STATUS_BASE;
-        let h_reg = regs::func::SEESAW_STATUS_TEMP;
-        let delay = STD_PROCESSING_DELAY_MICROS;
-
+    pub fn get_temp(&mut self) -> Result<f32, E> {
         let mut buffer = [0u8; 4];
-        self.read(l_reg, h_reg, &mut buffer[..], delay)?;
+        self.seesaw.read_register(
+            regs::base::SEESAW_STATUS_BASE,
+            regs::func::SEESAW_STATUS_TEMP,
+            &mut buffer,
+            STD_PROCESSING_DELAY_MICROS,
+        )?;
         let tmp_val = i32::from_be_bytes(buffer) as f32;
 
         // See: https://github.com/adafruit/Adafruit_Seesaw/blob/8728936a5d1a0a7bf2887a82adb0828b70556a45/Adafruit_seesaw.cpp#L664
@@ -110,6 +177,15 @@ impl<D: DelayUs<u16>> SoilSensor<D> {
         Ok(temp_celsius)
     }
 
+    /// Reads the seesaw firmware version and product ID off the status module.
+    ///
+    /// Useful to assert you're actually talking to a genuine STEMMA soil sensor
+    /// (product id [`STEMMA_SOIL_SENSOR_PRODUCT_ID`]) rather than relying only on the HW ID
+    /// check already done in [`SoilSensor::new`].
+    pub fn get_version(&mut self) -> Result<SeesawVersion, E> {
+        self.seesaw.get_version()
+    }
+
     /// Read the value of the moisture sensor
     ///
     /// The values ranges from 200 (very dry) to 2000 (very wet).
@@ -117,100 +193,168 @@ impl<D: DelayUs<u16>> SoilSensor<D> {
     /// # Errors
     /// This method will try to read the value from the sensors 3 times before
     /// it returns a `SoilSensErr::MoistureReadErr` if no read is successful.
-    pub fn get_capacitance(&mut self) -> Result<u16> {
+    pub fn get_capacitance(&mut self) -> Result<u16, E> {
         let l_reg: u8 = regs::base::SEESAW_TOUCH_BASE;
         let h_reg: u8 = regs::touch::SEESAW_TOUCH_CHANNEL_OFFSET;
         let mut buff = [0u8; 2];
         let mut retry_counter = 0;
 
         while retry_counter < 3 {
-            self.delay.delay_us(1000);
+            self.seesaw.delay_us(1000);
             // NB! Setting this to 1000 (like in the C library) errors.
-            if let Err(e) = self.read(l_reg, h_reg, &mut buff, 5000) {
-                debug!("Error reading capacitance: {}. Retry: {}", e, retry_counter + 1);
+            if let Err(e) = self.seesaw.read_register(l_reg, h_reg, &mut buff, 5000) {
+                debug!("Error reading capacitance: {:?}. Retry: {}", e, retry_counter + 1);
                 retry_counter += 1;
                 continue;
             }
 
             // A read before the chip is ready will be 0xFFFF
             let cap = u16::from_be_bytes(buff);
-            if cap < u16::max_value() {
+            if cap < u16::MAX {
                 return Ok(cap);
             }
+            retry_counter += 1;
         }
 
         Err(SoilSensErr::MoistureReadErr)
     }
 
-    /// Read an arbitrary I2C register range on the device.
+    /// Reads the moisture sensor and maps it to a 0-100 % value using the current
+    /// [`Calibration`], clamping readings outside the calibrated dry/wet range.
+    pub fn get_moisture_percent(&mut self) -> Result<f32, E> {
+        let cap = self.get_capacitance()?;
+        Ok(self.calibration.percent(cap))
+    }
+
+    /// Returns the calibration currently used by [`get_moisture_percent`](Self::get_moisture_percent).
+    pub fn calibration(&self) -> Calibration {
+        self.calibration
+    }
+
+    /// Sets the dry/wet calibration used by [`get_moisture_percent`](Self::get_moisture_percent).
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    /// Samples the current capacitance reading and stores it as the "dry" calibration point.
     ///
-    /// Delay is needed to allow the board to process the request.
-    fn read(&mut self, reg_low: u8, reg_high: u8, buff: &mut [u8], delay_us: u16) -> Result<()> {
-        self.channel.write(&[reg_low, reg_high])?;
-        self.delay.delay_us(delay_us);
-        self.channel.read(buff)?;
-        debug!("Received: {:?}", buff);
+    /// Call this with the sensor in dry soil (or air).
+    pub fn calibrate_dry(&mut self) -> Result<(), E> {
+        self.calibration.dry = self.get_capacitance()?;
         Ok(())
     }
-}
 
-mod init {
-    use super::*;
+    /// Samples the current capacitance reading and stores it as the "wet" calibration point.
+    ///
+    /// Call this with the sensor in fully saturated soil (or water).
+    pub fn calibrate_wet(&mut self) -> Result<(), E> {
+        self.calibration.wet = self.get_capacitance()?;
+        Ok(())
+    }
 
-    /// Initialize the channel
-    pub fn channel_init<D: DelayUs<u16>>(delay: &mut D, chan: &mut I2c) -> Result<()> {
-        match try_read_chan(chan, delay) {
-            Ok(resp) => {
-                debug!("Found device with HW id: {}", resp);
-                if resp != regs::SEESAW_HW_ID_CODE {
-                    return Err(SoilSensErr::HardwareMismatch(regs::SEESAW_HW_ID_CODE, resp));
-                } else {
-                    debug!("HW ID match: exp {}, got: {}", resp, regs::SEESAW_HW_ID_CODE);
-                    return Ok(());
-                }
-            }
+    /// Triggers a seesaw software reset, writing `0xFF` to `SEESAW_STATUS_SWRST`.
+    ///
+    /// The chip needs a moment to come back up afterwards, which this waits out using the
+    /// standard processing delay.
+    pub fn software_reset(&mut self) -> Result<(), E> {
+        self.seesaw.software_reset()
+    }
 
-            Err(SoilSensErr::I2C {
-                source: I2CError::InvalidSlaveAddress(adr),
-            }) => {
-                debug!("Invalid address: {}", adr);
-                return Err(SoilSensErr::InvalidSlaveAddress(adr));
-            }
+    /// Reprograms the sensor's I2C address in its seesaw EEPROM and resets it so the new
+    /// address takes effect, letting several identical STEMMA sensors share one bus outside
+    /// the four factory addresses (`0x36..=0x39`).
+    ///
+    /// Once this returns, `self` talks to the sensor at `new_addr`; the old address is no
+    /// longer valid for this device until it's reprogrammed again.
+    pub fn set_i2c_address(&mut self, new_addr: u8) -> Result<(), E> {
+        self.seesaw.set_i2c_address(new_addr)
+    }
+}
+
+/// Convenience constructor for Raspberry Pi users, backed by `rppal`.
+///
+/// This mirrors the pre-`embedded_hal` API: it opens `/dev/i2c-1` itself and scans the factory
+/// address range (`0x36..=0x39`) for the first sensor that responds.
+#[cfg(feature = "raspberry-pi")]
+impl<D: DelayUs<u16>> SoilSensor<rppal::i2c::I2c, D> {
+    /// Initializes the sensor by scanning the standard STEMMA soil sensor address range.
+    pub fn init(delay: D) -> Result<Self, rppal::i2c::Error> {
+        let i2c = rppal::i2c::I2c::new().map_err(SoilSensErr::I2C)?;
+        let mut seesaw = Seesaw::new(i2c, SENSOR_START_ADDR, delay);
+        let mut found = false;
 
-            Err(e) => {
-                debug!("Unexpected err: {}", e);
-                return Err(e);
+        for addr in SENSOR_START_ADDR..=SENSOR_END_ADDR {
+            debug!("Connecting to adr: {:#X}", addr);
+            seesaw.set_address(addr);
+            match seesaw.check_hw_id() {
+                Ok(()) => {
+                    found = true;
+                    break;
+                }
+                Err(SoilSensErr::HardwareMismatch(..)) => continue,
+                Err(SoilSensErr::I2C(rppal::i2c::Error::InvalidSlaveAddress(adr))) => {
+                    debug!("Invalid address: {}", adr);
+                    continue;
+                }
+                Err(e) => return Err(e),
             }
         }
-    }
 
-    // The fallible initialization code which we'll call for the entire valid address range
-    fn try_read_chan<D: DelayUs<u16>>(chan: &mut I2c, delay: &mut D) -> Result<u8> {
-        let reg_high = regs::base::SEESAW_STATUS_BASE;
-        let reg_low = regs::func::SEESAW_STATUS_HW_ID;
-        chan.write(&[reg_high, reg_low])?;
-        let mut buffer = [0];
-        delay.delay_us(STD_PROCESSING_DELAY_MICROS);
-
-        chan.read(&mut buffer)?;
-        debug!("Got: {:?}", buffer);
-        Ok(buffer[0])
+        if !found {
+            return Err(SoilSensErr::HwNotFound);
+        }
+        Ok(SoilSensor { seesaw, calibration: Calibration::default() })
     }
 }
 
 #[derive(Debug, Error)]
-pub enum SoilSensErr {
+pub enum SoilSensErr<E: core::fmt::Debug> {
     #[error("Couldn't get a valid reading from the moisture sensor.")]
     MoistureReadErr,
     #[error("Couldn't connect to the sensor.")]
     HwNotFound,
     #[error("Invalid Hardware ID. Expected {0}, got {1}")]
     HardwareMismatch(u8, u8),
-    #[error("invalid slave address: {0:#X}")]
-    InvalidSlaveAddress(u16),
-    #[error("I2C connection error. {source}")]
-    I2C {
-        #[from]
-        source: i2c::Error,
-    },
+    #[error("I2C connection error. {0:?}")]
+    I2C(E),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockI2c, NoopDelay};
+
+    #[test]
+    fn new_rejects_hardware_mismatch() {
+        let i2c = MockI2c::new(vec![vec![0x00]]);
+        let result = SoilSensor::new(i2c, 0x36, NoopDelay);
+        assert!(matches!(result, Err(SoilSensErr::HardwareMismatch(regs::SEESAW_HW_ID_CODE, 0x00))));
+    }
+
+    #[test]
+    fn new_accepts_matching_hardware_id() {
+        let i2c = MockI2c::new(vec![vec![regs::SEESAW_HW_ID_CODE]]);
+        assert!(SoilSensor::new(i2c, 0x36, NoopDelay).is_ok());
+    }
+
+    #[test]
+    fn calibration_percent_maps_linearly_and_clamps() {
+        let cal = Calibration { dry: 200, wet: 2000 };
+        assert_eq!(cal.percent(200), 0.0);
+        assert_eq!(cal.percent(2000), 100.0);
+        assert_eq!(cal.percent(1100), 50.0);
+        assert_eq!(cal.percent(0), 0.0); // clamped below dry
+        assert_eq!(cal.percent(u16::MAX), 100.0); // clamped above wet
+    }
+
+    #[test]
+    fn calibration_percent_handles_degenerate_range_without_nan() {
+        let cal = Calibration { dry: 500, wet: 500 };
+        assert_eq!(cal.percent(500), 100.0);
+        assert_eq!(cal.percent(499), 0.0);
+
+        let cal = Calibration { dry: 500, wet: 400 };
+        assert_eq!(cal.percent(500), 100.0);
+        assert_eq!(cal.percent(450), 0.0);
+    }
 }
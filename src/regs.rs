@@ -5,16 +5,42 @@ pub mod base {
     //! Module Base Addreses
     //! The module base addresses for different seesaw modules.
     pub const SEESAW_STATUS_BASE: u8 = 0x00;
+    pub const SEESAW_GPIO_BASE: u8 = 0x01;
+    pub const SEESAW_ADC_BASE: u8 = 0x09;
     pub const SEESAW_TOUCH_BASE: u8 = 0x0F;
 }
 
 pub mod func {
     //! status module function addres registers
     pub const SEESAW_STATUS_HW_ID: u8 = 0x01;
+    pub const SEESAW_STATUS_VERSION: u8 = 0x02;
     pub const SEESAW_STATUS_TEMP: u8 = 0x04;
+    pub const SEESAW_STATUS_SWRST: u8 = 0x7F;
 }
 
 pub mod touch {
     //! Touch module function addres registers
     pub const SEESAW_TOUCH_CHANNEL_OFFSET: u8 = 0x10;
 }
+
+pub mod gpio {
+    //! GPIO module function addres registers
+    pub const SEESAW_GPIO_DIRSET_BULK: u8 = 0x02;
+    pub const SEESAW_GPIO_BULK: u8 = 0x04;
+    pub const SEESAW_GPIO_BULK_SET: u8 = 0x05;
+    pub const SEESAW_GPIO_BULK_CLR: u8 = 0x06;
+}
+
+pub mod adc {
+    //! ADC module function addres registers
+    pub const SEESAW_ADC_CHANNEL_OFFSET: u8 = 0x07;
+}
+
+pub mod eeprom {
+    //! EEPROM module base address and function address registers
+    pub const SEESAW_EEPROM_BASE: u8 = 0x0D;
+    /// The chip's I2C address lives in the last EEPROM byte; `0x00`-`0x3E` are general-purpose
+    /// storage, so writing anywhere else silently corrupts user data instead of reprogramming
+    /// the address.
+    pub const SEESAW_EEPROM_I2C_ADDR: u8 = 0x3F;
+}
@@ -0,0 +1,274 @@
+//! Generic seesaw register layer.
+//!
+//! The seesaw chip used on the STEMMA soil sensor is a general purpose co-processor exposing
+//! several modules (status, GPIO, ADC, touch, EEPROM, ...), each addressed as a
+//! `(module_base, function)` register pair. [`Seesaw`] is the reusable core that talks to any
+//! of them over the raw I2C bus; higher level drivers like [`crate::SoilSensor`] layer typed,
+//! module-specific methods (temperature, capacitance, calibration, ...) on top of it. This lets
+//! the same core drive both the STEMMA soil sensor and the bare seesaw breakout many of these
+//! sensors are built on.
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+
+use crate::{regs, Result, SeesawVersion, SoilSensErr, STD_PROCESSING_DELAY_MICROS};
+
+/// A seesaw chip on an `embedded_hal` I2C bus, addressed generically by `(module_base,
+/// function)` register pairs.
+pub struct Seesaw<I2C, D> {
+    i2c: I2C,
+    address: u8,
+    delay: D,
+}
+
+impl<I2C, D, E> Seesaw<I2C, D>
+where
+    I2C: Write<Error = E> + Read<Error = E> + WriteRead<Error = E>,
+    D: DelayUs<u16>,
+    E: core::fmt::Debug,
+{
+    /// Wraps a bus and slave address. This does not talk to the chip; use
+    /// [`check_hw_id`](Self::check_hw_id) to confirm it's actually there.
+    pub fn new(i2c: I2C, address: u8, delay: D) -> Self {
+        Seesaw { i2c, address, delay }
+    }
+
+    /// The slave address this instance currently talks to.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Changes the slave address this instance talks to, without touching the chip. Useful
+    /// when probing several candidate addresses on the same bus, e.g. during a factory-address
+    /// scan; use [`set_i2c_address`](Self::set_i2c_address) instead to actually reprogram the
+    /// chip's own EEPROM address.
+    pub fn set_address(&mut self, address: u8) {
+        self.address = address;
+    }
+
+    /// Reads the HW ID off the status module and checks it matches `regs::SEESAW_HW_ID_CODE`.
+    pub fn check_hw_id(&mut self) -> Result<(), E> {
+        let mut buffer = [0u8; 1];
+        self.read_register(
+            regs::base::SEESAW_STATUS_BASE,
+            regs::func::SEESAW_STATUS_HW_ID,
+            &mut buffer,
+            STD_PROCESSING_DELAY_MICROS,
+        )?;
+        debug!("Found device with HW id: {}", buffer[0]);
+        if buffer[0] != regs::SEESAW_HW_ID_CODE {
+            return Err(SoilSensErr::HardwareMismatch(regs::SEESAW_HW_ID_CODE, buffer[0]));
+        }
+        Ok(())
+    }
+
+    /// Reads the seesaw firmware version and product ID off the status module.
+    pub fn get_version(&mut self) -> Result<SeesawVersion, E> {
+        let mut buffer = [0u8; 4];
+        self.read_register(
+            regs::base::SEESAW_STATUS_BASE,
+            regs::func::SEESAW_STATUS_VERSION,
+            &mut buffer,
+            STD_PROCESSING_DELAY_MICROS,
+        )?;
+        let raw = u32::from_be_bytes(buffer);
+        Ok(SeesawVersion { product_id: (raw >> 16) as u16, build_date: raw as u16 })
+    }
+
+    /// Triggers a seesaw software reset, writing `0xFF` to `SEESAW_STATUS_SWRST`.
+    ///
+    /// The chip needs a moment to come back up afterwards, which this waits out using the
+    /// standard processing delay.
+    pub fn software_reset(&mut self) -> Result<(), E> {
+        self.write_register(regs::base::SEESAW_STATUS_BASE, regs::func::SEESAW_STATUS_SWRST, &[0xFF])?;
+        self.delay.delay_us(STD_PROCESSING_DELAY_MICROS);
+        Ok(())
+    }
+
+    /// Reprograms the chip's I2C address in its EEPROM and resets it so the new address takes
+    /// effect, updating `self` to talk to the sensor at `new_addr` afterwards.
+    pub fn set_i2c_address(&mut self, new_addr: u8) -> Result<(), E> {
+        self.write_register(regs::eeprom::SEESAW_EEPROM_BASE, regs::eeprom::SEESAW_EEPROM_I2C_ADDR, &[new_addr])?;
+        self.software_reset()?;
+        self.address = new_addr;
+        Ok(())
+    }
+
+    /// Configures the given pins (as a bitmask) as GPIO outputs.
+    pub fn gpio_set_direction(&mut self, pin_mask: u32) -> Result<(), E> {
+        self.write_register(regs::base::SEESAW_GPIO_BASE, regs::gpio::SEESAW_GPIO_DIRSET_BULK, &pin_mask.to_be_bytes())
+    }
+
+    /// Drives the given output pins (as a bitmask) high.
+    pub fn gpio_set(&mut self, pin_mask: u32) -> Result<(), E> {
+        self.write_register(regs::base::SEESAW_GPIO_BASE, regs::gpio::SEESAW_GPIO_BULK_SET, &pin_mask.to_be_bytes())
+    }
+
+    /// Drives the given output pins (as a bitmask) low.
+    pub fn gpio_clear(&mut self, pin_mask: u32) -> Result<(), E> {
+        self.write_register(regs::base::SEESAW_GPIO_BASE, regs::gpio::SEESAW_GPIO_BULK_CLR, &pin_mask.to_be_bytes())
+    }
+
+    /// Reads the current level of every GPIO pin as a bitmask.
+    pub fn gpio_read(&mut self) -> Result<u32, E> {
+        let mut buffer = [0u8; 4];
+        self.read_register(
+            regs::base::SEESAW_GPIO_BASE,
+            regs::gpio::SEESAW_GPIO_BULK,
+            &mut buffer,
+            STD_PROCESSING_DELAY_MICROS,
+        )?;
+        Ok(u32::from_be_bytes(buffer))
+    }
+
+    /// Reads the raw value of ADC `channel`.
+    pub fn adc_read_channel(&mut self, channel: u8) -> Result<u16, E> {
+        let mut buffer = [0u8; 2];
+        self.read_register(
+            regs::base::SEESAW_ADC_BASE,
+            regs::adc::SEESAW_ADC_CHANNEL_OFFSET + channel,
+            &mut buffer,
+            STD_PROCESSING_DELAY_MICROS,
+        )?;
+        Ok(u16::from_be_bytes(buffer))
+    }
+
+    /// Blocks for `delay_us` microseconds, letting the chip process a request.
+    pub(crate) fn delay_us(&mut self, delay_us: u16) {
+        self.delay.delay_us(delay_us);
+    }
+
+    /// Read an arbitrary `(module_base, function)` register on the device.
+    ///
+    /// `delay_us` gives the chip time to process the request between the write and the read.
+    pub(crate) fn read_register(
+        &mut self,
+        module_base: u8,
+        function: u8,
+        buff: &mut [u8],
+        delay_us: u16,
+    ) -> Result<(), E> {
+        self.i2c.write(self.address, &[module_base, function]).map_err(SoilSensErr::I2C)?;
+        self.delay.delay_us(delay_us);
+        self.i2c.read(self.address, buff).map_err(SoilSensErr::I2C)?;
+        debug!("Received: {:?}", buff);
+        Ok(())
+    }
+
+    /// Write to an arbitrary `(module_base, function)` register on the device. `data` is at
+    /// most 4 bytes, which covers every register this driver writes (bitmasks, addresses, and
+    /// single status bytes).
+    pub(crate) fn write_register(&mut self, module_base: u8, function: u8, data: &[u8]) -> Result<(), E> {
+        let mut buf = [0u8; 6];
+        let len = 2 + data.len();
+        buf[0] = module_base;
+        buf[1] = function;
+        buf[2..len].copy_from_slice(data);
+        self.i2c.write(self.address, &buf[..len]).map_err(SoilSensErr::I2C)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockI2c, NoopDelay};
+
+    #[test]
+    fn set_i2c_address_writes_the_eeprom_address_register() {
+        let i2c = MockI2c::new(vec![]);
+        let mut seesaw = Seesaw::new(i2c, 0x36, NoopDelay);
+
+        seesaw.set_i2c_address(0x37).unwrap();
+
+        assert_eq!(
+            seesaw.i2c.writes()[0],
+            (0x36, vec![regs::eeprom::SEESAW_EEPROM_BASE, regs::eeprom::SEESAW_EEPROM_I2C_ADDR, 0x37])
+        );
+        assert_eq!(seesaw.address(), 0x37);
+    }
+
+    #[test]
+    fn get_version_splits_product_id_and_build_date() {
+        // product_id = 4026 (0x0FBA), build_date = 0x1234.
+        let i2c = MockI2c::new(vec![vec![0x0F, 0xBA, 0x12, 0x34]]);
+        let mut seesaw = Seesaw::new(i2c, 0x36, NoopDelay);
+
+        let version = seesaw.get_version().unwrap();
+
+        assert_eq!(version.product_id, 4026);
+        assert_eq!(version.build_date, 0x1234);
+    }
+
+    #[test]
+    fn software_reset_writes_swrst_register() {
+        let i2c = MockI2c::new(vec![]);
+        let mut seesaw = Seesaw::new(i2c, 0x36, NoopDelay);
+
+        seesaw.software_reset().unwrap();
+
+        assert_eq!(
+            seesaw.i2c.writes()[0],
+            (0x36, vec![regs::base::SEESAW_STATUS_BASE, regs::func::SEESAW_STATUS_SWRST, 0xFF])
+        );
+    }
+
+    #[test]
+    fn gpio_set_direction_writes_dirset_bulk_register() {
+        let i2c = MockI2c::new(vec![]);
+        let mut seesaw = Seesaw::new(i2c, 0x36, NoopDelay);
+
+        seesaw.gpio_set_direction(0x0000_0001).unwrap();
+
+        assert_eq!(
+            seesaw.i2c.writes()[0],
+            (0x36, vec![regs::base::SEESAW_GPIO_BASE, regs::gpio::SEESAW_GPIO_DIRSET_BULK, 0x00, 0x00, 0x00, 0x01])
+        );
+    }
+
+    #[test]
+    fn gpio_set_writes_bulk_set_register() {
+        let i2c = MockI2c::new(vec![]);
+        let mut seesaw = Seesaw::new(i2c, 0x36, NoopDelay);
+
+        seesaw.gpio_set(0x0000_0001).unwrap();
+
+        assert_eq!(
+            seesaw.i2c.writes()[0],
+            (0x36, vec![regs::base::SEESAW_GPIO_BASE, regs::gpio::SEESAW_GPIO_BULK_SET, 0x00, 0x00, 0x00, 0x01])
+        );
+    }
+
+    #[test]
+    fn gpio_clear_writes_bulk_clr_register() {
+        let i2c = MockI2c::new(vec![]);
+        let mut seesaw = Seesaw::new(i2c, 0x36, NoopDelay);
+
+        seesaw.gpio_clear(0x0000_0001).unwrap();
+
+        assert_eq!(
+            seesaw.i2c.writes()[0],
+            (0x36, vec![regs::base::SEESAW_GPIO_BASE, regs::gpio::SEESAW_GPIO_BULK_CLR, 0x00, 0x00, 0x00, 0x01])
+        );
+    }
+
+    #[test]
+    fn gpio_read_decodes_bulk_register() {
+        let i2c = MockI2c::new(vec![vec![0x00, 0x00, 0x00, 0x05]]);
+        let mut seesaw = Seesaw::new(i2c, 0x36, NoopDelay);
+
+        assert_eq!(seesaw.gpio_read().unwrap(), 0x05);
+    }
+
+    #[test]
+    fn adc_read_channel_offsets_by_channel_and_decodes_value() {
+        let i2c = MockI2c::new(vec![vec![0x01, 0xF4]]);
+        let mut seesaw = Seesaw::new(i2c, 0x36, NoopDelay);
+
+        let value = seesaw.adc_read_channel(2).unwrap();
+
+        assert_eq!(value, 0x01F4);
+        assert_eq!(
+            seesaw.i2c.writes()[0],
+            (0x36, vec![regs::base::SEESAW_ADC_BASE, regs::adc::SEESAW_ADC_CHANNEL_OFFSET + 2])
+        );
+    }
+}
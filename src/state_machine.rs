@@ -0,0 +1,263 @@
+//! Non-blocking polling mode.
+//!
+//! `SoilSensor::get_temp`/`get_capacitance` block on `embedded_hal` delays while the chip
+//! processes each request. That's fine for a single sensor, but with several sensors on one
+//! bus the blocking delays stack up linearly. [`SensorStateMachine`] replaces the internal
+//! delays with an explicit state machine advanced by [`SensorStateMachine::poll`]: each call
+//! performs at most one I2C write or read and compares a caller-supplied monotonic timestamp
+//! against the chip's processing delay instead of sleeping. [`MultiSensor`] drives a `Vec` of
+//! these machines over a single shared bus so many sensors can be ticked together without their
+//! delays adding up.
+use core::task::Poll;
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+
+use crate::{regs, SoilSensErr, STD_PROCESSING_DELAY_MICROS};
+
+// Matches the `delay_us(.., 5000)` wait `SoilSensor::get_capacitance` gives the touch module
+// before reading it back.
+const TOUCH_PROCESSING_DELAY_MICROS: u64 = 5000;
+const MAX_TOUCH_RETRIES: u8 = 3;
+
+/// A combined temperature + capacitance reading, produced once a full poll cycle completes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reading {
+    pub temp: f32,
+    pub capacitance: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Idle,
+    TempRequested { since_us: u64 },
+    TempReady { temp: f32 },
+    TouchRequested { since_us: u64, temp: f32, retries: u8 },
+    TouchResendPending { temp: f32, retries: u8 },
+}
+
+/// Drives a single sensor through `Idle -> TempRequested -> TempReady -> TouchRequested` without
+/// blocking; a touch read that isn't ready yet goes through `TouchResendPending` to re-select the
+/// register and wait again. Call [`poll`](Self::poll) on a fixed cadence (e.g. every 50 ms)
+/// with a monotonic timestamp in microseconds; it returns `Poll::Ready` once a full
+/// temperature + capacitance reading has been gathered, and goes back to `Idle` to start the
+/// next cycle.
+pub struct SensorStateMachine {
+    address: u8,
+    state: State,
+}
+
+impl SensorStateMachine {
+    /// Creates a new state machine for the sensor at `address`, starting in the `Idle` state.
+    pub fn new(address: u8) -> Self {
+        SensorStateMachine { address, state: State::Idle }
+    }
+
+    /// The I2C slave address this state machine is driving.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Advances the state machine by at most one I2C operation.
+    ///
+    /// `now_us` must come from a monotonic clock and be in the same units across calls.
+    pub fn poll<I2C, E>(&mut self, i2c: &mut I2C, now_us: u64) -> Poll<crate::Result<Reading, E>>
+    where
+        I2C: Write<Error = E> + Read<Error = E> + WriteRead<Error = E>,
+        E: core::fmt::Debug,
+    {
+        match self.state {
+            State::Idle => match request_temp(i2c, self.address) {
+                Ok(()) => {
+                    self.state = State::TempRequested { since_us: now_us };
+                    Poll::Pending
+                }
+                Err(e) => Poll::Ready(Err(SoilSensErr::I2C(e))),
+            },
+
+            State::TempRequested { since_us } => {
+                if elapsed(now_us, since_us) < STD_PROCESSING_DELAY_MICROS as u64 {
+                    return Poll::Pending;
+                }
+                match read_temp(i2c, self.address) {
+                    Ok(temp) => {
+                        self.state = State::TempReady { temp };
+                        Poll::Pending
+                    }
+                    Err(e) => {
+                        self.state = State::Idle;
+                        Poll::Ready(Err(SoilSensErr::I2C(e)))
+                    }
+                }
+            }
+
+            State::TempReady { temp } => match request_touch(i2c, self.address) {
+                Ok(()) => {
+                    self.state = State::TouchRequested { since_us: now_us, temp, retries: 0 };
+                    Poll::Pending
+                }
+                Err(e) => {
+                    self.state = State::Idle;
+                    Poll::Ready(Err(SoilSensErr::I2C(e)))
+                }
+            },
+
+            State::TouchRequested { since_us, temp, retries } => {
+                if elapsed(now_us, since_us) < TOUCH_PROCESSING_DELAY_MICROS {
+                    return Poll::Pending;
+                }
+                match read_touch(i2c, self.address) {
+                    Ok(capacitance) if capacitance < u16::MAX => {
+                        self.state = State::Idle;
+                        Poll::Ready(Ok(Reading { temp, capacitance }))
+                    }
+                    Ok(_not_ready_yet) if retries < MAX_TOUCH_RETRIES => {
+                        // Mirrors the blocking `get_capacitance`, which re-sends the register
+                        // select write on every retry rather than re-reading the same buffer.
+                        self.state = State::TouchResendPending { temp, retries: retries + 1 };
+                        Poll::Pending
+                    }
+                    Ok(_not_ready_yet) => {
+                        self.state = State::Idle;
+                        Poll::Ready(Err(SoilSensErr::MoistureReadErr))
+                    }
+                    Err(e) => {
+                        self.state = State::Idle;
+                        Poll::Ready(Err(SoilSensErr::I2C(e)))
+                    }
+                }
+            }
+
+            State::TouchResendPending { temp, retries } => match request_touch(i2c, self.address) {
+                Ok(()) => {
+                    self.state = State::TouchRequested { since_us: now_us, temp, retries };
+                    Poll::Pending
+                }
+                Err(e) => {
+                    self.state = State::Idle;
+                    Poll::Ready(Err(SoilSensErr::I2C(e)))
+                }
+            },
+        }
+    }
+}
+
+fn elapsed(now_us: u64, since_us: u64) -> u64 {
+    now_us.saturating_sub(since_us)
+}
+
+fn request_temp<I2C, E>(i2c: &mut I2C, address: u8) -> core::result::Result<(), E>
+where
+    I2C: Write<Error = E>,
+{
+    i2c.write(address, &[regs::base::SEESAW_STATUS_BASE, regs::func::SEESAW_STATUS_TEMP])
+}
+
+fn read_temp<I2C, E>(i2c: &mut I2C, address: u8) -> core::result::Result<f32, E>
+where
+    I2C: Read<Error = E>,
+{
+    let mut buffer = [0u8; 4];
+    i2c.read(address, &mut buffer)?;
+    let tmp_val = i32::from_be_bytes(buffer) as f32;
+    Ok((1.0 / (1u32 << 16) as f32) * tmp_val)
+}
+
+fn request_touch<I2C, E>(i2c: &mut I2C, address: u8) -> core::result::Result<(), E>
+where
+    I2C: Write<Error = E>,
+{
+    i2c.write(address, &[regs::base::SEESAW_TOUCH_BASE, regs::touch::SEESAW_TOUCH_CHANNEL_OFFSET])
+}
+
+fn read_touch<I2C, E>(i2c: &mut I2C, address: u8) -> core::result::Result<u16, E>
+where
+    I2C: Read<Error = E>,
+{
+    let mut buffer = [0u8; 2];
+    i2c.read(address, &mut buffer)?;
+    Ok(u16::from_be_bytes(buffer))
+}
+
+/// Ticks several [`SensorStateMachine`]s over a single shared I2C bus, interleaving their states
+/// so the aggregate time to read N sensors stays flat instead of growing with N.
+pub struct MultiSensor<I2C> {
+    i2c: I2C,
+    sensors: Vec<SensorStateMachine>,
+}
+
+impl<I2C, E> MultiSensor<I2C>
+where
+    I2C: Write<Error = E> + Read<Error = E> + WriteRead<Error = E>,
+    E: core::fmt::Debug,
+{
+    /// Creates a multi-sensor poller for the given slave addresses, all sharing `i2c`.
+    pub fn new(i2c: I2C, addresses: impl IntoIterator<Item = u8>) -> Self {
+        let sensors = addresses.into_iter().map(SensorStateMachine::new).collect();
+        MultiSensor { i2c, sensors }
+    }
+
+    /// Advances every sensor's state machine by one step, in the order the addresses were given
+    /// to [`new`](Self::new).
+    pub fn poll(&mut self, now_us: u64) -> Vec<(u8, Poll<crate::Result<Reading, E>>)> {
+        let i2c = &mut self.i2c;
+        self.sensors
+            .iter_mut()
+            .map(|sensor| (sensor.address(), sensor.poll(i2c, now_us)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::MockI2c;
+
+    #[test]
+    fn full_cycle_yields_reading() {
+        // temp = 0.0, then capacitance = 500 served on the first touch read.
+        let mut i2c = MockI2c::new(vec![vec![0, 0, 0, 0], vec![0x01, 0xF4]]);
+        let mut sensor = SensorStateMachine::new(0x36);
+
+        assert!(matches!(sensor.poll(&mut i2c, 0), Poll::Pending)); // Idle -> TempRequested
+        assert!(matches!(sensor.poll(&mut i2c, 0), Poll::Pending)); // still waiting on delay
+        let after_temp_delay = STD_PROCESSING_DELAY_MICROS as u64;
+        assert!(matches!(sensor.poll(&mut i2c, after_temp_delay), Poll::Pending)); // TempRequested -> TempReady
+        assert!(matches!(sensor.poll(&mut i2c, after_temp_delay), Poll::Pending)); // TempReady -> TouchRequested
+
+        let after_touch_delay = after_temp_delay + TOUCH_PROCESSING_DELAY_MICROS;
+        match sensor.poll(&mut i2c, after_touch_delay) {
+            Poll::Ready(Ok(reading)) => {
+                assert_eq!(reading.temp, 0.0);
+                assert_eq!(reading.capacitance, 0x01F4);
+            }
+            other => panic!("expected a ready reading, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn touch_not_ready_resends_register_select_then_succeeds() {
+        // First touch read isn't ready (u16::MAX sentinel), second is.
+        let mut i2c = MockI2c::new(vec![vec![0, 0, 0, 0], vec![0xFF, 0xFF], vec![0x02, 0x58]]);
+        let mut sensor = SensorStateMachine::new(0x36);
+
+        let mut now = 0u64;
+        assert!(matches!(sensor.poll(&mut i2c, now), Poll::Pending)); // Idle -> TempRequested
+        now += STD_PROCESSING_DELAY_MICROS as u64;
+        assert!(matches!(sensor.poll(&mut i2c, now), Poll::Pending)); // TempRequested -> TempReady
+        assert!(matches!(sensor.poll(&mut i2c, now), Poll::Pending)); // TempReady -> TouchRequested
+
+        now += TOUCH_PROCESSING_DELAY_MICROS;
+        assert!(matches!(sensor.poll(&mut i2c, now), Poll::Pending)); // not ready -> TouchResendPending
+        assert!(matches!(sensor.poll(&mut i2c, now), Poll::Pending)); // TouchResendPending -> TouchRequested
+
+        now += TOUCH_PROCESSING_DELAY_MICROS;
+        match sensor.poll(&mut i2c, now) {
+            Poll::Ready(Ok(reading)) => assert_eq!(reading.capacitance, 0x0258),
+            other => panic!("expected a ready reading, got {:?}", other),
+        }
+
+        // The register select write must be re-sent on every touch retry, not just the read.
+        let touch_select = (0x36, vec![regs::base::SEESAW_TOUCH_BASE, regs::touch::SEESAW_TOUCH_CHANNEL_OFFSET]);
+        let touch_writes: Vec<_> = i2c.writes().iter().filter(|w| **w == touch_select).collect();
+        assert_eq!(touch_writes.len(), 2);
+    }
+}
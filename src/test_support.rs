@@ -0,0 +1,61 @@
+//! Test-only `embedded_hal` I2C/delay mocks shared across the crate's unit tests.
+#![cfg(test)]
+
+use std::collections::VecDeque;
+
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+
+/// A bare-bones `embedded_hal` I2C mock: writes are recorded (so tests can assert on them) and
+/// always accepted, and reads are served from a queue of pre-programmed responses, one per
+/// expected read call.
+pub(crate) struct MockI2c {
+    responses: VecDeque<Vec<u8>>,
+    writes: Vec<(u8, Vec<u8>)>,
+}
+
+impl MockI2c {
+    pub(crate) fn new(responses: Vec<Vec<u8>>) -> Self {
+        MockI2c { responses: responses.into(), writes: Vec::new() }
+    }
+
+    /// The `(address, bytes)` of every write performed so far, in order.
+    pub(crate) fn writes(&self) -> &[(u8, Vec<u8>)] {
+        &self.writes
+    }
+}
+
+impl Write for MockI2c {
+    type Error = core::convert::Infallible;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.writes.push((address, bytes.to_vec()));
+        Ok(())
+    }
+}
+
+impl Read for MockI2c {
+    type Error = core::convert::Infallible;
+
+    fn read(&mut self, _address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let response = self.responses.pop_front().expect("MockI2c: no more responses queued");
+        buffer.copy_from_slice(&response);
+        Ok(())
+    }
+}
+
+impl WriteRead for MockI2c {
+    type Error = core::convert::Infallible;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.write(address, bytes)?;
+        self.read(address, buffer)
+    }
+}
+
+/// A no-op `embedded_hal` delay, for tests that don't care about real timing.
+pub(crate) struct NoopDelay;
+
+impl DelayUs<u16> for NoopDelay {
+    fn delay_us(&mut self, _us: u16) {}
+}